@@ -0,0 +1,603 @@
+use bootloader::bootinfo::MemoryRegionType;
+use bootloader::BootInfo;
+use core::arch::asm;
+use core::fmt;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::{hlt, interrupts};
+use x86_64::registers::control::{Cr0, Cr2, Cr3, Cr4};
+use x86_64::registers::rflags;
+
+use crate::utils::collections::String;
+use crate::utils::crc::crc32;
+use crate::{serial, vga_buffer, warn};
+
+const MESSAGE_LEN: usize = 160;
+const STACK_DUMP_WORDS: usize = 18;
+const BACKTRACE_DEPTH: usize = 8;
+/// Largest distance between two saved frame pointers that is still taken
+/// for a frame; anything further away ends the walk.
+const MAX_FRAME_SIZE: u64 = 64 * 1024;
+
+/// What the kernel does after reporting a panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanicAction {
+    /// Stop the CPU with interrupts disabled.
+    Halt = 0,
+    /// Reset the machine through the keyboard controller, falling back to a
+    /// triple fault.
+    Reboot = 1,
+}
+
+static PANIC_ACTION: AtomicU8 = AtomicU8::new(PanicAction::Halt as u8);
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_panic_action(action: PanicAction) {
+    PANIC_ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+pub fn panic_action() -> PanicAction {
+    match PANIC_ACTION.load(Ordering::Relaxed) {
+        1 => PanicAction::Reboot,
+        _ => PanicAction::Halt,
+    }
+}
+
+/// Register contents as seen by the code that captured them.
+///
+/// Taken inside `handle_panic`, so the general-purpose registers, RSP and RBP
+/// describe its own frame rather than the faulting instruction. The
+/// `Backtrace` and `StackDump` in the report cover the code that panicked.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct RegisterSnapshot {
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rbp: u64,
+    pub rsp: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub rflags: u64,
+    pub cr0: u64,
+    pub cr2: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+}
+
+impl RegisterSnapshot {
+    #[inline(always)]
+    pub fn capture() -> RegisterSnapshot {
+        let mut registers = RegisterSnapshot::default();
+        unsafe {
+            asm!(
+                "mov [{0} + 0x00], rax",
+                "mov [{0} + 0x08], rbx",
+                "mov [{0} + 0x10], rcx",
+                "mov [{0} + 0x18], rdx",
+                "mov [{0} + 0x20], rsi",
+                "mov [{0} + 0x28], rdi",
+                "mov [{0} + 0x30], rbp",
+                "mov [{0} + 0x38], rsp",
+                "mov [{0} + 0x40], r8",
+                "mov [{0} + 0x48], r9",
+                "mov [{0} + 0x50], r10",
+                "mov [{0} + 0x58], r11",
+                "mov [{0} + 0x60], r12",
+                "mov [{0} + 0x68], r13",
+                "mov [{0} + 0x70], r14",
+                "mov [{0} + 0x78], r15",
+                in(reg) &mut registers as *mut RegisterSnapshot,
+                options(nostack, preserves_flags),
+            );
+        }
+        registers.rflags = rflags::read_raw();
+        registers.cr0 = Cr0::read_raw();
+        registers.cr2 = Cr2::read_raw();
+        let (frame, flags) = Cr3::read_raw();
+        registers.cr3 = frame.start_address().as_u64() | flags as u64;
+        registers.cr4 = Cr4::read_raw();
+        registers
+    }
+}
+
+impl fmt::Display for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registers = [
+            ("RAX", self.rax),
+            ("RBX", self.rbx),
+            ("RCX", self.rcx),
+            ("RDX", self.rdx),
+            ("RSI", self.rsi),
+            ("RDI", self.rdi),
+            ("RBP", self.rbp),
+            ("RSP", self.rsp),
+            ("R8", self.r8),
+            ("R9", self.r9),
+            ("R10", self.r10),
+            ("R11", self.r11),
+            ("R12", self.r12),
+            ("R13", self.r13),
+            ("R14", self.r14),
+            ("R15", self.r15),
+            ("RFL", self.rflags),
+            ("CR0", self.cr0),
+            ("CR2", self.cr2),
+            ("CR3", self.cr3),
+            ("CR4", self.cr4),
+        ];
+        // three per line keeps the dump within the 80 column VGA buffer
+        for line in registers.chunks(3) {
+            for (name, value) in line {
+                write!(f, "{:>3}={:016x}  ", name, value)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// One stack frame found by following the saved frame pointers.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct Frame {
+    /// Where the frame's saved RBP is stored.
+    pub frame_pointer: u64,
+    pub return_address: u64,
+}
+
+impl Frame {
+    /// Lowest address of the calling function's frame, i.e. its stack
+    /// pointer at the time of the call.
+    pub fn caller_stack(&self) -> u64 {
+        self.frame_pointer + 16
+    }
+}
+
+/// Return addresses of the innermost frames, from the RBP chain.
+///
+/// This relies on frame pointers, which the target spec turns on for the
+/// kernel and `core` alike: every RBP points at the caller's saved RBP,
+/// followed by the return address.
+#[derive(Debug, Clone, Copy)]
+pub struct Backtrace {
+    frames: [Frame; BACKTRACE_DEPTH],
+    len: usize,
+}
+
+impl Backtrace {
+    /// Walks the frames starting with the one of the calling function.
+    #[inline(always)]
+    pub fn capture() -> Backtrace {
+        let frame_pointer: u64;
+        unsafe {
+            asm!("mov {}, rbp", out(reg) frame_pointer, options(nomem, nostack, preserves_flags));
+        }
+        unsafe { Backtrace::walk(frame_pointer) }
+    }
+
+    /// `frame_pointer` must point at a saved RBP on a mapped stack.
+    unsafe fn walk(mut frame_pointer: u64) -> Backtrace {
+        let mut backtrace = Backtrace {
+            frames: [Frame::default(); BACKTRACE_DEPTH],
+            len: 0,
+        };
+        while backtrace.len < BACKTRACE_DEPTH {
+            let slot = frame_pointer as *const u64;
+            let return_address = core::ptr::read_volatile(slot.add(1));
+            if return_address == 0 {
+                break;
+            }
+            backtrace.frames[backtrace.len] = Frame {
+                frame_pointer,
+                return_address,
+            };
+            backtrace.len += 1;
+
+            // the stack grows down, so callers' frames lie above; anything
+            // else means the chain left the kernel's code
+            let next = core::ptr::read_volatile(slot);
+            if next <= frame_pointer || next - frame_pointer > MAX_FRAME_SIZE || next & 7 != 0 {
+                break;
+            }
+            frame_pointer = next;
+        }
+        backtrace
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames[..self.len]
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Backtrace:")?;
+        for line in self.frames().chunks(4) {
+            write!(f, " ")?;
+            for frame in line {
+                write!(f, " {:#018x}", frame.return_address)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Quadwords read upwards from a stack pointer.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct StackDump {
+    pub address: u64,
+    pub words: [u64; STACK_DUMP_WORDS],
+}
+
+impl StackDump {
+    /// Reads `STACK_DUMP_WORDS` quadwords starting at `address`, which must
+    /// be mapped.
+    unsafe fn read(address: u64) -> StackDump {
+        let mut words = [0u64; STACK_DUMP_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = core::ptr::read_volatile((address as *const u64).add(i));
+        }
+        StackDump { address, words }
+    }
+}
+
+impl fmt::Display for StackDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Stack at {:#x}:", self.address)?;
+        for (i, line) in self.words.chunks(3).enumerate() {
+            write!(f, "  {:016x}:", self.address + (i * 3 * 8) as u64)?;
+            for word in line {
+                write!(f, " {:016x}", word)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything kept about a panic for the next boot.
+#[derive(Clone, Copy)]
+pub struct CrashRecord {
+    /// The panic message, cut off after `MESSAGE_LEN` bytes.
+    pub message: String<MESSAGE_LEN>,
+    pub registers: RegisterSnapshot,
+    pub backtrace: Backtrace,
+    pub stack: StackDump,
+}
+
+impl fmt::Display for CrashRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "KERNEL PANIC: {}", self.message)?;
+        write!(f, "{}{}{}", self.registers, self.backtrace, self.stack)
+    }
+}
+
+/// Copies as much of a message as fits, cutting it between characters.
+struct Truncating<'a> {
+    message: &'a mut String<MESSAGE_LEN>,
+    full: bool,
+}
+
+impl fmt::Write for Truncating<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.full || self.message.push(c).is_err() {
+                // later, shorter pieces must not be appended after the cut
+                self.full = true;
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+const CRASH_MAGIC: u64 = u64::from_be_bytes(*b"TINYCRSH");
+/// Bytes at the start of `StoredCrash` that its checksum does not cover.
+const CRASH_HEADER_LEN: usize = 12;
+
+/// `CrashRecord` laid out so it can be read back from memory holding
+/// anything, e.g. after a cold boot: every bit pattern is a valid value, and
+/// `load` checks the magic value, the CRC-32 and all lengths.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct StoredCrash {
+    magic: u64,
+    /// CRC-32 of everything after this field.
+    crc: u32,
+    message_len: u32,
+    message: [u8; MESSAGE_LEN],
+    registers: RegisterSnapshot,
+    frames: [Frame; BACKTRACE_DEPTH],
+    frame_count: u64,
+    stack: StackDump,
+}
+
+impl StoredCrash {
+    fn new(record: &CrashRecord) -> StoredCrash {
+        let mut message = [0u8; MESSAGE_LEN];
+        message[..record.message.len()].copy_from_slice(record.message.as_bytes());
+        let mut stored = StoredCrash {
+            magic: CRASH_MAGIC,
+            crc: 0,
+            message_len: record.message.len() as u32,
+            message,
+            registers: record.registers,
+            frames: record.backtrace.frames,
+            frame_count: record.backtrace.len as u64,
+            stack: record.stack,
+        };
+        stored.crc = stored.checksum();
+        stored
+    }
+
+    fn checksum(&self) -> u32 {
+        // all fields are multiples of 4 bytes and 8-byte aligned from
+        // `message_len` on, so there is no padding to hash
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                self as *const StoredCrash as *const u8,
+                core::mem::size_of::<StoredCrash>(),
+            )
+        };
+        crc32(&bytes[CRASH_HEADER_LEN..])
+    }
+
+    /// Returns the record, if this holds a complete one.
+    fn load(&self) -> Option<CrashRecord> {
+        if self.magic != CRASH_MAGIC || self.crc != self.checksum() {
+            return None;
+        }
+        let text = self.message.get(..self.message_len as usize)?;
+        let mut message = String::new();
+        message.push_str(core::str::from_utf8(text).ok()?).ok()?;
+        if self.frame_count > BACKTRACE_DEPTH as u64 {
+            return None;
+        }
+        Some(CrashRecord {
+            message,
+            registers: self.registers,
+            backtrace: Backtrace {
+                frames: self.frames,
+                len: self.frame_count as usize,
+            },
+            stack: self.stack,
+        })
+    }
+}
+
+/// Virtual address of the `StoredCrash` area, or 0 before `init`.
+static CRASH_AREA: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref LAST_CRASH: Mutex<Option<CrashRecord>> = Mutex::new(None);
+}
+
+/// Sets up the crash record area and reports a record left there by the
+/// previous boot.
+///
+/// The area is the last frame of usable RAM. The bootloader hands out frames
+/// from the bottom, and neither the 8042 reset nor the triple fault used by
+/// `reboot` clears memory, so a record survives into the next boot; a cold
+/// boot leaves garbage that fails the checksum. A frame allocator must keep
+/// this frame for itself once the kernel has one.
+pub fn init(boot_info: &'static BootInfo) {
+    let end = boot_info
+        .memory_map
+        .iter()
+        .filter(|region| region.region_type == MemoryRegionType::Usable)
+        .map(|region| region.range.end_addr())
+        .max();
+    let frame = match end {
+        Some(end) if end >= 4096 => end - 4096,
+        _ => return,
+    };
+    let area = (boot_info.physical_memory_offset + frame) as *mut StoredCrash;
+
+    let stored = unsafe { core::ptr::read_volatile(area) };
+    if let Some(record) = stored.load() {
+        warn!("previous boot panicked: {}", record.message);
+        *LAST_CRASH.lock() = Some(record);
+    }
+    // report each crash only once
+    unsafe { core::ptr::write_volatile(core::ptr::addr_of_mut!((*area).magic), 0) };
+    CRASH_AREA.store(area as u64, Ordering::SeqCst);
+}
+
+/// Returns the record the previous boot left behind, if it ended in a panic
+/// followed by a warm reset.
+pub fn last_crash() -> Option<CrashRecord> {
+    *LAST_CRASH.lock()
+}
+
+/// Reports a panic on the console and the serial port, stores a
+/// `CrashRecord` for the next boot (see `init`) and then carries out the
+/// configured `PanicAction`.
+///
+/// Meant to be called from the `#[panic_handler]`; the stack dump starts at
+/// the frame that called the panic handler, right below the panicking code.
+#[inline(never)]
+pub fn handle_panic(info: &PanicInfo) -> ! {
+    use core::fmt::Write;
+
+    interrupts::disable();
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        // panicked again while reporting; don't risk another round
+        halt();
+    }
+
+    let registers = RegisterSnapshot::capture();
+    let backtrace = Backtrace::capture();
+    // frames()[0] is this function, [1] the panic handler
+    let stack_start = match backtrace.frames() {
+        [_, handler, ..] => handler.caller_stack(),
+        [this] => this.caller_stack(),
+        [] => registers.rsp,
+    };
+    let stack = unsafe { StackDump::read(stack_start) };
+
+    let mut message = String::new();
+    let _ = write!(
+        Truncating {
+            message: &mut message,
+            full: false,
+        },
+        "{}",
+        info
+    );
+    let record = CrashRecord {
+        message,
+        registers,
+        backtrace,
+        stack,
+    };
+    let area = CRASH_AREA.load(Ordering::SeqCst);
+    if area != 0 {
+        unsafe { core::ptr::write_volatile(area as *mut StoredCrash, StoredCrash::new(&record)) };
+    }
+
+    // the panic may have hit while one of the output locks was held, and
+    // nothing else runs anymore
+    unsafe {
+        vga_buffer::WRITER.force_unlock();
+        serial::SERIAL1.force_unlock();
+    }
+
+    let action = panic_action();
+    let action_text = match action {
+        PanicAction::Halt => "halting",
+        PanicAction::Reboot => "rebooting",
+    };
+    vga_buffer::_print(format_args!(
+        "KERNEL PANIC: {}\n{}{}{}System {}.\n",
+        info, registers, backtrace, stack, action_text
+    ));
+    serial::_print(format_args!(
+        "KERNEL PANIC: {}\n{}{}{}System {}.\n",
+        info, registers, backtrace, stack, action_text
+    ));
+
+    match action {
+        PanicAction::Halt => halt(),
+        PanicAction::Reboot => reboot(),
+    }
+}
+
+fn halt() -> ! {
+    interrupts::disable();
+    loop {
+        hlt();
+    }
+}
+
+/// Resets the machine.
+pub fn reboot() -> ! {
+    use x86_64::instructions::port::Port;
+    use x86_64::structures::idt::InterruptDescriptorTable;
+
+    interrupts::disable();
+    unsafe {
+        // pulse the CPU reset line through the 8042 keyboard controller
+        let mut command: Port<u8> = Port::new(0x64);
+        command.write(0xfe);
+    }
+
+    // no reset yet: with an empty IDT, the next exception triple faults
+    lazy_static! {
+        static ref EMPTY_IDT: InterruptDescriptorTable = InterruptDescriptorTable::new();
+    }
+    EMPTY_IDT.load();
+    interrupts::int3();
+    halt()
+}
+
+#[test_case]
+fn test_register_snapshot_capture() {
+    let registers = RegisterSnapshot::capture();
+    let local = 0u64;
+    let local_address = &local as *const u64 as u64;
+    // the snapshot's stack pointer must be below our own locals
+    assert!(registers.rsp != 0 && registers.rsp <= local_address);
+    // paging is always on in long mode
+    assert!(registers.cr0 & (1 << 31) != 0);
+}
+
+#[cfg(test)]
+#[inline(never)]
+fn caller_stack_contains(local: &u64) -> bool {
+    let backtrace = Backtrace::capture();
+    let stack = unsafe { StackDump::read(backtrace.frames()[0].caller_stack()) };
+    stack.words.contains(local)
+}
+
+#[test_case]
+fn test_stack_dump_reads_caller_frame() {
+    // a volatile store keeps the value in this function's frame instead of
+    // letting the compiler promote it to a constant
+    let mut marker = 0u64;
+    unsafe { core::ptr::write_volatile(&mut marker, 0x7469_6e79_5f6f_7321) };
+    assert!(caller_stack_contains(&marker));
+    assert!(Backtrace::capture().frames().len() >= 2);
+}
+
+#[test_case]
+fn test_crash_message_truncates_between_characters() {
+    use core::fmt::Write;
+
+    let mut message = String::new();
+    let mut writer = Truncating {
+        message: &mut message,
+        full: false,
+    };
+    write!(
+        writer,
+        "{:x<1$}{2}{3}",
+        "",
+        MESSAGE_LEN - 4,
+        "0123456789",
+        "z"
+    )
+    .unwrap();
+    assert_eq!(message.len(), MESSAGE_LEN);
+    assert!(message.as_str().ends_with("xxxx0123"));
+}
+
+#[test_case]
+fn test_stored_crash_round_trip() {
+    let mut message = String::new();
+    message.push_str("panicked at 'test'").unwrap();
+    let record = CrashRecord {
+        message,
+        registers: RegisterSnapshot::capture(),
+        backtrace: Backtrace::capture(),
+        stack: StackDump {
+            address: 0x1000,
+            words: [0x5a; STACK_DUMP_WORDS],
+        },
+    };
+    let mut stored = StoredCrash::new(&record);
+    let loaded = stored.load().unwrap();
+    assert_eq!(loaded.message, "panicked at 'test'");
+    assert_eq!(loaded.registers.rsp, record.registers.rsp);
+    assert_eq!(
+        loaded.backtrace.frames().len(),
+        record.backtrace.frames().len()
+    );
+
+    stored.stack.words[3] ^= 1;
+    assert!(stored.load().is_none());
+}
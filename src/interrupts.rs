@@ -1,5 +1,5 @@
 use crate::gdt;
-use crate::println;
+use crate::{info, warn};
 use lazy_static::lazy_static;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 
@@ -18,10 +18,18 @@ lazy_static! {
 
 pub fn init_idt() {
     IDT.load();
+    info!("loaded IDT");
 }
 
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
+    warn!(
+        "EXCEPTION: BREAKPOINT rip={:#x} rsp={:#x} rflags={:#x} cs={:#x} ss={:#x}",
+        stack_frame.instruction_pointer.as_u64(),
+        stack_frame.stack_pointer.as_u64(),
+        stack_frame.cpu_flags,
+        stack_frame.code_segment,
+        stack_frame.stack_segment
+    );
 }
 
 extern "x86-interrupt" fn double_fault_handler(
@@ -0,0 +1,424 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+use spin::{Mutex, MutexGuard};
+
+use crate::utils::collections::{FnvMap, String};
+use crate::utils::ring_buffer::MpscRingBuffer;
+use crate::{println, serial_println};
+
+const LOG_CAPACITY: usize = 128;
+const PENDING_CAPACITY: usize = 32;
+const MESSAGE_LEN: usize = 120;
+const MAX_TARGET_LEVELS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Most verbose level compiled in at all; log calls above it are removed by
+/// the compiler. Chosen with the `max_level_*` cargo features.
+pub const STATIC_MAX_LEVEL: Option<Level> = if cfg!(feature = "max_level_off") {
+    None
+} else if cfg!(feature = "max_level_error") {
+    Some(Level::Error)
+} else if cfg!(feature = "max_level_warn") {
+    Some(Level::Warn)
+} else if cfg!(feature = "max_level_info") {
+    Some(Level::Info)
+} else if cfg!(feature = "max_level_debug") {
+    Some(Level::Debug)
+} else {
+    Some(Level::Trace)
+};
+
+/// Returns whether `level` passes the compile-time filter.
+#[inline]
+pub const fn statically_enabled(level: Level) -> bool {
+    match STATIC_MAX_LEVEL {
+        Some(max) => level as u8 <= max as u8,
+        None => false,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Entry {
+    timestamp: u64,
+    level: Level,
+    target: &'static str,
+    message: String<MESSAGE_LEN>,
+}
+
+impl Entry {
+    const fn empty() -> Entry {
+        Entry {
+            timestamp: 0,
+            level: Level::Trace,
+            target: "",
+            message: String::new(),
+        }
+    }
+
+    /// Time stamp counter value at the moment the entry was recorded.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Module path of the code that logged the entry.
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Write for Entry {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // truncate overlong messages, but never in the middle of a character
+        for c in s.chars() {
+            if self.message.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:>14}] {:<5} {}: {}",
+            self.timestamp,
+            self.level,
+            self.target,
+            self.message()
+        )
+    }
+}
+
+/// Fixed-size ring buffer of log entries; once full, the oldest entry is
+/// overwritten.
+pub struct KernelLog {
+    entries: [Entry; LOG_CAPACITY],
+    next: usize,
+    len: usize,
+}
+
+impl KernelLog {
+    fn new() -> KernelLog {
+        KernelLog {
+            entries: [Entry::empty(); LOG_CAPACITY],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.entries[self.next] = entry;
+        self.next = (self.next + 1) % LOG_CAPACITY;
+        if self.len < LOG_CAPACITY {
+            self.len += 1;
+        }
+    }
+
+    /// Iterates over the retained entries, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        let start = (self.next + LOG_CAPACITY - self.len) % LOG_CAPACITY;
+        (0..self.len).map(move |i| &self.entries[(start + i) % LOG_CAPACITY])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// An `Option<Level>` that can be read and changed without locking, so
+/// logging from an exception handler never waits on the configuration.
+struct AtomicLevel(AtomicU8);
+
+impl AtomicLevel {
+    const OFF: u8 = u8::MAX;
+
+    const fn new(level: Option<Level>) -> AtomicLevel {
+        AtomicLevel(AtomicU8::new(match level {
+            Some(level) => level as u8,
+            None => AtomicLevel::OFF,
+        }))
+    }
+
+    fn load(&self) -> Option<Level> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => Some(Level::Error),
+            1 => Some(Level::Warn),
+            2 => Some(Level::Info),
+            3 => Some(Level::Debug),
+            4 => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    fn store(&self, level: Option<Level>) {
+        self.0.store(
+            level.map_or(AtomicLevel::OFF, |level| level as u8),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+// `MAX_LEVEL` and the per-target overrides decide whether a record is kept at
+// all; the sink levels decide where kept records are also printed.
+static MAX_LEVEL: AtomicLevel = AtomicLevel::new(Some(Level::Debug));
+// serial also carries test results, so boot-time INFO records stay off it
+// until someone asks for them
+static SERIAL_LEVEL: AtomicLevel = AtomicLevel::new(Some(Level::Warn));
+static CONSOLE_LEVEL: AtomicLevel = AtomicLevel::new(Some(Level::Warn));
+
+fn level_for(target: &str) -> Option<Level> {
+    let max_level = MAX_LEVEL.load();
+    // the override table is only locked while it is being changed; a record
+    // logged meanwhile (e.g. from an exception handler) uses the default level
+    // instead of spinning on the lock
+    match TARGET_LEVELS.try_lock() {
+        Some(targets) => targets
+            .iter()
+            .filter(|(prefix, _)| target_matches(target, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(max_level, |(_, &level)| level),
+        None => max_level,
+    }
+}
+
+/// Returns whether `target` is the module `prefix` or one of its submodules.
+fn target_matches(target: &str, prefix: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+lazy_static! {
+    static ref KERNEL_LOG: Mutex<KernelLog> = Mutex::new(KernelLog::new());
+    /// Records not yet moved into `KERNEL_LOG`. Logging pushes here without
+    /// locking, so an exception handler can log while the history is locked.
+    static ref PENDING: MpscRingBuffer<Entry, PENDING_CAPACITY> = MpscRingBuffer::new();
+    /// Module path prefixes with their own level; the longest match wins.
+    static ref TARGET_LEVELS: Mutex<FnvMap<&'static str, Option<Level>, MAX_TARGET_LEVELS>> =
+        Mutex::new(FnvMap::new());
+}
+
+/// Records lost because the pending queue was full.
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+fn drain_pending(log: &mut KernelLog) {
+    while let Some(entry) = PENDING.pop() {
+        log.push(entry);
+    }
+}
+
+/// Locks the kernel log history, after moving pending records into it.
+pub fn lock() -> MutexGuard<'static, KernelLog> {
+    let mut log = KERNEL_LOG.lock();
+    drain_pending(&mut log);
+    log
+}
+
+/// Number of records lost because the history stayed locked while the
+/// pending queue filled up.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Sets the default runtime level; `None` disables logging.
+pub fn set_max_level(level: Option<Level>) {
+    MAX_LEVEL.store(level);
+}
+
+/// Overrides the runtime level for the module `prefix`, e.g.
+/// `"tiny_os::interrupts"`, and its submodules. Returns false if the
+/// override table is full.
+pub fn set_target_level(prefix: &'static str, level: Option<Level>) -> bool {
+    TARGET_LEVELS.lock().insert(prefix, level).is_ok()
+}
+
+/// Removes an override set with `set_target_level`.
+pub fn clear_target_level(prefix: &str) {
+    TARGET_LEVELS.lock().remove(prefix);
+}
+
+/// Sets the most verbose level printed to the serial port; `None` disables
+/// serial output. Records stay in the ring buffer either way.
+pub fn set_serial_level(level: Option<Level>) {
+    SERIAL_LEVEL.store(level);
+}
+
+/// Sets the most verbose level echoed to the VGA console.
+pub fn set_console_level(level: Option<Level>) {
+    CONSOLE_LEVEL.store(level);
+}
+
+fn timestamp() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[macro_export]
+macro_rules! klog {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::klog::statically_enabled($level) {
+            $crate::klog::_log($level, module_path!(), format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::klog!($crate::klog::Level::Error, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => ($crate::klog!($crate::klog::Level::Warn, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => ($crate::klog!($crate::klog::Level::Info, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => ($crate::klog!($crate::klog::Level::Debug, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::klog!($crate::klog::Level::Trace, $($arg)*));
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, target: &'static str, args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    if !matches!(level_for(target), Some(max) if level <= max) {
+        return;
+    }
+
+    let mut entry = Entry::empty();
+    entry.timestamp = timestamp();
+    entry.level = level;
+    entry.target = target;
+    // overlong messages are truncated by `write_str`, and a failing argument
+    // only leaves the message short; logging must never panic
+    let _ = entry.write_fmt(args);
+
+    if PENDING.push(entry).is_err() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    if let Some(mut log) = KERNEL_LOG.try_lock() {
+        drain_pending(&mut log);
+    }
+
+    if matches!(SERIAL_LEVEL.load(), Some(max) if level <= max) {
+        serial_println!("{}", entry);
+    }
+    if matches!(CONSOLE_LEVEL.load(), Some(max) if level <= max) {
+        println!("{}", entry);
+    }
+}
+
+/// Prints all retained entries at `max_level` or more severe to the console.
+pub fn dmesg(max_level: Level) {
+    let log = lock();
+    for entry in log.iter().filter(|entry| entry.level <= max_level) {
+        println!("{}", entry);
+    }
+}
+
+#[test_case]
+fn test_klog_records_entry() {
+    debug!("test_klog_records_entry {}", 42);
+    let log = lock();
+    let entry = log.iter().last().unwrap();
+    assert_eq!(entry.level(), Level::Debug);
+    assert_eq!(entry.target(), "tiny_os::klog");
+    assert_eq!(entry.message(), "test_klog_records_entry 42");
+}
+
+#[test_case]
+fn test_klog_overwrites_oldest() {
+    for i in 0..LOG_CAPACITY + 1 {
+        klog!(Level::Debug, "test_klog_overwrites_oldest {}", i);
+    }
+    let log = lock();
+    assert_eq!(log.len(), LOG_CAPACITY);
+    assert_eq!(
+        log.iter().next().unwrap().message(),
+        "test_klog_overwrites_oldest 1"
+    );
+}
+
+#[test_case]
+fn test_klog_truncates_long_message() {
+    klog!(Level::Debug, "{:x<1$}", "", MESSAGE_LEN + 10);
+    let log = lock();
+    assert_eq!(log.iter().last().unwrap().message().len(), MESSAGE_LEN);
+}
+
+#[test_case]
+fn test_klog_target_level_override() {
+    trace!("test_klog_target_level_override hidden");
+    assert_ne!(
+        lock().iter().last().unwrap().message(),
+        "test_klog_target_level_override hidden"
+    );
+
+    assert!(set_target_level("tiny_os::klog", Some(Level::Trace)));
+    trace!("test_klog_target_level_override shown");
+    clear_target_level("tiny_os::klog");
+    assert_eq!(
+        lock().iter().last().unwrap().message(),
+        "test_klog_target_level_override shown"
+    );
+}
+
+#[test_case]
+fn test_klog_target_matches_module_boundary() {
+    assert!(target_matches("tiny_os::gdt", "tiny_os::gdt"));
+    assert!(target_matches("tiny_os::gdt::tss", "tiny_os::gdt"));
+    assert!(!target_matches("tiny_os::gdt_foo", "tiny_os::gdt"));
+    assert!(!target_matches("tiny_os::interrupts", "tiny_os::int"));
+}
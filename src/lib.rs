@@ -5,12 +5,18 @@
 #![reexport_test_harness_main = "test_main"]
 #![feature(abi_x86_interrupt)]
 
+pub mod crash;
 pub mod gdt;
 pub mod interrupts;
+pub mod klog;
 pub mod serial;
+pub mod utils;
 pub mod vga_buffer;
 
+use core::fmt;
 use core::panic::PanicInfo;
+use lazy_static::lazy_static;
+use spin::Mutex;
 
 pub fn init() {
     gdt::init();
@@ -19,6 +25,7 @@ pub fn init() {
 
 pub trait Testable {
     fn run(&self) -> ();
+    fn name(&self) -> &'static str;
 }
 
 impl<T> Testable for T
@@ -26,27 +33,185 @@ where
     T: Fn(),
 {
     fn run(&self) {
-        serial_print!("{}...\t", core::any::type_name::<T>());
         self();
-        serial_println!("[ok]");
+    }
+
+    fn name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+}
+
+/// Only tests whose name contains this string are run. Set at build time,
+/// e.g. `TINY_OS_TEST_FILTER=vga_buffer cargo test`.
+const TEST_FILTER: Option<&str> = option_env!("TINY_OS_TEST_FILTER");
+
+/// Test output format, selected at build time with `TINY_OS_TEST_FORMAT`
+/// set to `human` (the default) or `tap`, in any case. Other values fail
+/// the build.
+const TEST_FORMAT: Option<&str> = option_env!("TINY_OS_TEST_FORMAT");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestOutput {
+    Human,
+    /// Test Anything Protocol, for host scripts collecting results
+    Tap,
+}
+
+const TEST_OUTPUT: TestOutput = match parse_test_format(TEST_FORMAT) {
+    Some(output) => output,
+    None => panic!("TINY_OS_TEST_FORMAT must be `human` or `tap`"),
+};
+
+const fn parse_test_format(format: Option<&str>) -> Option<TestOutput> {
+    match format {
+        None => Some(TestOutput::Human),
+        Some(format) if format.is_empty() || eq_ignore_ascii_case(format, "human") => {
+            Some(TestOutput::Human)
+        }
+        Some(format) if eq_ignore_ascii_case(format, "tap") => Some(TestOutput::Tap),
+        Some(_) => None,
+    }
+}
+
+const fn eq_ignore_ascii_case(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if !a[i].eq_ignore_ascii_case(&b[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+fn test_output() -> TestOutput {
+    TEST_OUTPUT
+}
+
+fn test_selected(name: &str, filter: Option<&str>) -> bool {
+    match filter {
+        Some(filter) => name.contains(filter),
+        None => true,
     }
 }
 
+lazy_static! {
+    /// Number and name of the running test, for reporting from the panic handler.
+    static ref CURRENT_TEST: Mutex<(usize, &'static str)> = Mutex::new((0, ""));
+}
+
 pub fn test_runner(tests: &[&dyn Testable]) {
-    serial_println!("Running {} tests", tests.len());
-    for test in tests {
+    // the serial port carries the test results, keep log records out of it
+    klog::set_serial_level(None);
+
+    let output = test_output();
+    let selected = || {
+        tests
+            .iter()
+            .filter(|test| test_selected(test.name(), TEST_FILTER))
+    };
+
+    let count = selected().count();
+    match output {
+        TestOutput::Human if count < tests.len() => {
+            serial_println!(
+                "Running {} tests ({} filtered out)",
+                count,
+                tests.len() - count
+            );
+        }
+        TestOutput::Human => {
+            serial_println!("Running {} tests", count);
+        }
+        TestOutput::Tap => {
+            serial_println!("1..{}", count);
+        }
+    }
+
+    for (index, test) in selected().enumerate() {
+        let number = index + 1;
+        *CURRENT_TEST.lock() = (number, test.name());
+        if output == TestOutput::Human {
+            serial_print!("{}...\t", test.name());
+        }
         test.run();
+        match output {
+            TestOutput::Human => {
+                serial_println!("[ok]");
+            }
+            TestOutput::Tap => {
+                serial_println!("ok {} - {}", number, test.name());
+            }
+        }
     }
     exit_qemu(QemuExitCode::Success);
 }
 
+/// Writes to serial, starting every new line with a TAP diagnostic marker.
+struct TapDiagnostic;
+
+impl fmt::Write for TapDiagnostic {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                serial_print!("\n# ");
+            }
+            serial_print!("{}", line);
+        }
+        Ok(())
+    }
+}
+
 pub fn test_panic_handler(info: &PanicInfo) -> ! {
-    serial_println!("[failed]\n");
-    serial_println!("Error: {}\n", info);
+    match test_output() {
+        TestOutput::Human => {
+            serial_println!("[failed]\n");
+            serial_println!("Error: {}\n", info);
+        }
+        TestOutput::Tap => {
+            use core::fmt::Write;
+
+            let (number, name) = *CURRENT_TEST.lock();
+            serial_println!("not ok {} - {}", number, name);
+            serial_print!("# ");
+            let _ = write!(TapDiagnostic, "Error: {}", info);
+            serial_println!();
+            serial_println!("Bail out!");
+        }
+    }
     exit_qemu(QemuExitCode::Failed);
     loop {}
 }
 
+#[test_case]
+fn test_format_selection() {
+    assert_eq!(parse_test_format(None), Some(TestOutput::Human));
+    assert_eq!(parse_test_format(Some("tap")), Some(TestOutput::Tap));
+    assert_eq!(parse_test_format(Some("TAP")), Some(TestOutput::Tap));
+    assert_eq!(parse_test_format(Some("Human")), Some(TestOutput::Human));
+    assert_eq!(parse_test_format(Some("json")), None);
+}
+
+#[test_case]
+fn test_filter_selection() {
+    assert!(test_selected(
+        "tiny_os::vga_buffer::test_println_simple",
+        None
+    ));
+    assert!(test_selected(
+        "tiny_os::vga_buffer::test_println_simple",
+        Some("vga_buffer")
+    ));
+    assert!(!test_selected(
+        "tiny_os::klog::test_klog_records_entry",
+        Some("vga_buffer")
+    ));
+}
+
 /// Entry point for cargo test
 
 #[cfg(test)]
@@ -4,6 +4,7 @@
 #![test_runner(tiny_os::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
+use bootloader::BootInfo;
 use core::panic::PanicInfo;
 use tiny_os::println;
 
@@ -24,10 +25,11 @@ pub fn exit_qemu(exit_code: QemuExitCode) {
 }
 
 #[no_mangle]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
     println!("Hello World{}", "!");
 
     tiny_os::init();
+    tiny_os::crash::init(boot_info);
 
     #[cfg(test)]
     test_main();
@@ -40,8 +42,7 @@ pub extern "C" fn _start() -> ! {
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
-    loop {}
+    tiny_os::crash::handle_panic(info)
 }
 
 #[cfg(test)]
@@ -0,0 +1,5 @@
+pub mod collections;
+pub mod crc;
+pub mod crypto;
+pub mod fixed;
+pub mod ring_buffer;
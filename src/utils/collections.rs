@@ -0,0 +1,514 @@
+use core::borrow::Borrow;
+use core::hash::{Hash, Hasher};
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::{fmt, ptr, slice, str};
+
+/// The collections here have a fixed capacity and never allocate. When full,
+/// adding fails: the rejected value is handed back where there is one
+/// (`Vec::push`, `FnvMap::insert`), otherwise this error is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError;
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("collection is full")
+    }
+}
+
+pub struct Vec<T, const N: usize> {
+    buffer: [MaybeUninit<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> Vec<T, N> {
+    pub fn new() -> Vec<T, N> {
+        Vec {
+            buffer: core::array::from_fn(|_| MaybeUninit::uninit()),
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Appends `value`, handing it back if the vector is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        self.buffer[self.len].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.buffer[self.len].assume_init_read() })
+    }
+
+    /// Removes and returns the element at `index`, shifting the rest down.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "removal index out of bounds");
+        let value = unsafe { self.buffer[index].assume_init_read() };
+        self.buffer[index..self.len].rotate_left(1);
+        self.len -= 1;
+        value
+    }
+
+    /// Removes the element at `index` and moves the last element into its
+    /// place.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "removal index out of bounds");
+        self.buffer.swap(index, self.len - 1);
+        self.pop().unwrap()
+    }
+
+    /// Drops all elements from `len` onwards.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            self.pop();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.buffer.as_ptr() as *const T, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.buffer.as_mut_ptr() as *mut T, self.len) }
+    }
+}
+
+impl<T: Clone, const N: usize> Vec<T, N> {
+    /// Appends all of `values`, or nothing if they don't fit.
+    pub fn extend_from_slice(&mut self, values: &[T]) -> Result<(), CapacityError> {
+        if values.len() > N - self.len {
+            return Err(CapacityError);
+        }
+        for value in values {
+            let _ = self.push(value.clone());
+        }
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> Default for Vec<T, N> {
+    fn default() -> Self {
+        Vec::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Vec<T, N> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) };
+    }
+}
+
+impl<T, const N: usize> Deref for Vec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for Vec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for Vec<T, N> {
+    fn clone(&self) -> Self {
+        let mut clone = Vec::new();
+        for value in self.iter() {
+            let _ = clone.push(value.clone());
+        }
+        clone
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for Vec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for Vec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for Vec<T, N> {}
+
+/// UTF-8 string of at most `N` bytes.
+#[derive(Clone, Copy)]
+pub struct String<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> String<N> {
+    pub const fn new() -> String<N> {
+        String {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever filled from `str`s and `char`s, so always valid UTF-8
+        unsafe { str::from_utf8_unchecked(&self.bytes[..self.len]) }
+    }
+
+    pub fn push(&mut self, c: char) -> Result<(), CapacityError> {
+        self.push_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Appends all of `s`, or nothing if it doesn't fit.
+    pub fn push_str(&mut self, s: &str) -> Result<(), CapacityError> {
+        let end = self.len + s.len();
+        if end > N {
+            return Err(CapacityError);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.as_str().chars().next_back()?;
+        self.len -= c.len_utf8();
+        Some(c)
+    }
+
+    /// Shortens the string to `len` bytes.
+    ///
+    /// Panics if `len` is not on a character boundary.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            assert!(self.as_str().is_char_boundary(len));
+            self.len = len;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for String<N> {
+    fn default() -> Self {
+        String::new()
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for String<N> {
+    type Error = CapacityError;
+
+    fn try_from(s: &str) -> Result<Self, CapacityError> {
+        let mut string = String::new();
+        string.push_str(s)?;
+        Ok(string)
+    }
+}
+
+impl<const N: usize> Deref for String<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Write for String<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl<const N: usize> fmt::Display for String<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Debug for String<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize, const M: usize> PartialEq<String<M>> for String<N> {
+    fn eq(&self, other: &String<M>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const N: usize> Eq for String<N> {}
+
+impl<const N: usize> PartialEq<str> for String<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for String<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// 64-bit FNV-1a, a simple and fast hash for short keys.
+#[derive(Debug, Clone, Copy)]
+pub struct FnvHasher(u64);
+
+impl FnvHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub fn new() -> FnvHasher {
+        FnvHasher(Self::OFFSET_BASIS)
+    }
+}
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher::new()
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hash map with room for `N` entries, using FNV-1a and linear probing.
+pub struct FnvMap<K, V, const N: usize> {
+    slots: [Option<(K, V)>; N],
+    len: usize,
+}
+
+impl<K: Hash + Eq, V, const N: usize> FnvMap<K, V, N> {
+    pub fn new() -> FnvMap<K, V, N> {
+        FnvMap {
+            slots: core::array::from_fn(|_| None),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    fn ideal_slot<Q: Hash + ?Sized>(key: &Q) -> usize {
+        let mut hasher = FnvHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % N as u64) as usize
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if N == 0 {
+            return None;
+        }
+        let start = Self::ideal_slot(key);
+        for offset in 0..N {
+            let index = (start + offset) % N;
+            match &self.slots[index] {
+                Some((existing, _)) if existing.borrow() == key => return Some(index),
+                Some(_) => continue,
+                None => return None,
+            }
+        }
+        None
+    }
+
+    /// Inserts or replaces the value for `key`, returning the previous value.
+    /// Hands the pair back if the key is new and the map is full.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        if N == 0 {
+            return Err((key, value));
+        }
+        let start = Self::ideal_slot(&key);
+        for offset in 0..N {
+            let index = (start + offset) % N;
+            match &mut self.slots[index] {
+                Some((existing, old)) if *existing == key => {
+                    return Ok(Some(core::mem::replace(old, value)));
+                }
+                Some(_) => continue,
+                slot @ None => {
+                    *slot = Some((key, value));
+                    self.len += 1;
+                    return Ok(None);
+                }
+            }
+        }
+        Err((key, value))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.slots[index].as_ref().map(|(_, value)| value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.slots[index].as_mut().map(|(_, value)| value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut hole = self.find(key)?;
+        let (_, value) = self.slots[hole].take()?;
+        self.len -= 1;
+
+        // shift later entries of the probe sequence back so lookups don't
+        // stop early at the new hole
+        let mut index = hole;
+        loop {
+            index = (index + 1) % N;
+            let ideal = match &self.slots[index] {
+                Some((key, _)) => Self::ideal_slot(key),
+                None => break,
+            };
+            let distance_to_hole = (hole + N - ideal) % N;
+            let distance_to_index = (index + N - ideal) % N;
+            if distance_to_hole <= distance_to_index {
+                self.slots[hole] = self.slots[index].take();
+                hole = index;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = None;
+        }
+        self.len = 0;
+    }
+
+    /// Iterates over the entries in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots.iter().flatten().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> Default for FnvMap<K, V, N> {
+    fn default() -> Self {
+        FnvMap::new()
+    }
+}
+
+#[test_case]
+fn test_vec_push_pop_remove() {
+    let mut vec = Vec::<u32, 4>::new();
+    for value in 1..=4 {
+        assert_eq!(vec.push(value), Ok(()));
+    }
+    assert_eq!(vec.push(5), Err(5));
+    assert_eq!(vec.remove(1), 2);
+    assert_eq!(vec.as_slice(), &[1, 3, 4]);
+    assert_eq!(vec.swap_remove(0), 1);
+    assert_eq!(vec.as_slice(), &[4, 3]);
+    assert_eq!(vec.extend_from_slice(&[7, 8, 9]), Err(CapacityError));
+    assert_eq!(vec.extend_from_slice(&[7, 8]), Ok(()));
+    assert_eq!(vec.pop(), Some(8));
+    assert_eq!(vec.len(), 3);
+}
+
+#[test_case]
+fn test_string_capacity_and_write() {
+    use core::fmt::Write;
+
+    let mut string = String::<8>::new();
+    assert_eq!(string.push_str("tiny"), Ok(()));
+    assert_eq!(string.push_str("_os_rs"), Err(CapacityError));
+    assert_eq!(string, "tiny");
+    assert_eq!(write!(string, "{}", 42), Ok(()));
+    assert_eq!(string.push('é'), Ok(()));
+    assert_eq!(string, "tiny42é");
+    assert_eq!(string.pop(), Some('é'));
+    assert!(write!(string, "{}", 123).is_err());
+}
+
+#[test_case]
+fn test_fnv_map_insert_get_remove() {
+    let mut map = FnvMap::<&str, u32, 4>::new();
+    assert_eq!(map.insert("gdt", 1), Ok(None));
+    assert_eq!(map.insert("idt", 2), Ok(None));
+    assert_eq!(map.insert("gdt", 3), Ok(Some(1)));
+    assert_eq!(map.insert("tss", 4), Ok(None));
+    assert_eq!(map.insert("pic", 5), Ok(None));
+    assert_eq!(map.insert("apic", 6), Err(("apic", 6)));
+    assert_eq!(map.len(), 4);
+
+    assert_eq!(map.remove("idt"), Some(2));
+    assert_eq!(map.get("idt"), None);
+    for (key, value) in [("gdt", 3), ("tss", 4), ("pic", 5)] {
+        assert_eq!(map.get(key), Some(&value));
+    }
+    assert_eq!(map.insert("apic", 6), Ok(None));
+    assert_eq!(map.iter().count(), 4);
+}
@@ -0,0 +1,201 @@
+/// Reflected polynomial of CRC-32 (IEEE 802.3, zlib, PNG).
+const CRC32_POLYNOMIAL: u32 = 0xedb8_8320;
+/// Polynomial of CRC-16-CCITT, x^16 + x^12 + x^5 + 1.
+const CRC16_CCITT_POLYNOMIAL: u16 = 0x1021;
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+const CRC16_CCITT_TABLE: [u16; 256] = crc16_ccitt_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const fn crc16_ccitt_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ CRC16_CCITT_POLYNOMIAL
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Incremental CRC-32 for data that arrives in pieces.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 { state: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            let index = (self.state ^ byte as u32) & 0xff;
+            self.state = (self.state >> 8) ^ CRC32_TABLE[index as usize];
+        }
+    }
+
+    pub fn finalize(self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+/// Continues a CRC-16-CCITT computation from `crc`.
+pub fn crc16_ccitt_update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        let index = ((crc >> 8) ^ byte as u16) & 0xff;
+        crc = (crc << 8) ^ CRC16_CCITT_TABLE[index as usize];
+    }
+    crc
+}
+
+/// CRC-16-CCITT with initial value 0xFFFF (often called CCITT-FALSE).
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    crc16_ccitt_update(0xffff, data)
+}
+
+/// CRC-16-CCITT with initial value 0, as used by XMODEM-CRC.
+pub fn crc16_xmodem(data: &[u8]) -> u16 {
+    crc16_ccitt_update(0, data)
+}
+
+/// Incremental Internet checksum (RFC 1071), e.g. for a UDP or TCP
+/// pseudo-header followed by the segment. Pieces may have odd lengths; the
+/// data is summed as if it were one contiguous buffer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InternetChecksum {
+    // a u64 only overflows after 2^48 words, so folding once at the end is
+    // enough for any input
+    sum: u64,
+    /// Whether an odd number of bytes has been added so far.
+    odd: bool,
+}
+
+impl InternetChecksum {
+    pub fn new() -> InternetChecksum {
+        InternetChecksum { sum: 0, odd: false }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.odd {
+            // low byte of the word the previous piece started
+            if let Some((&first, rest)) = data.split_first() {
+                self.sum += first as u64;
+                self.odd = false;
+                data = rest;
+            }
+        }
+        let mut words = data.chunks_exact(2);
+        for word in &mut words {
+            self.sum += u16::from_be_bytes([word[0], word[1]]) as u64;
+        }
+        if let [last] = words.remainder() {
+            self.sum += (*last as u64) << 8;
+            self.odd = true;
+        }
+    }
+
+    /// Ones' complement of the ones' complement sum, with a trailing odd
+    /// byte padded with zero.
+    pub fn finalize(self) -> u16 {
+        let mut sum = self.sum;
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+/// Internet checksum (RFC 1071): ones' complement of the ones' complement
+/// sum of the data as big-endian 16-bit words, odd length padded with zero.
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    let mut checksum = InternetChecksum::new();
+    checksum.update(data);
+    checksum.finalize()
+}
+
+#[test_case]
+fn test_crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+    let mut crc = Crc32::new();
+    crc.update(b"1234");
+    crc.update(b"56789");
+    assert_eq!(crc.finalize(), 0xcbf4_3926);
+}
+
+#[test_case]
+fn test_crc16_check_values() {
+    assert_eq!(crc16_ccitt(b"123456789"), 0x29b1);
+    assert_eq!(crc16_xmodem(b"123456789"), 0x31c3);
+}
+
+#[test_case]
+fn test_internet_checksum_rfc1071_example() {
+    let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+    assert_eq!(internet_checksum(&data), 0x220d);
+    assert_eq!(internet_checksum(&data[..7]), 0x2304);
+}
+
+#[test_case]
+fn test_internet_checksum_odd_pieces() {
+    let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+    let mut checksum = InternetChecksum::new();
+    checksum.update(&data[..3]);
+    checksum.update(&[]);
+    checksum.update(&data[3..4]);
+    checksum.update(&data[4..]);
+    assert_eq!(checksum.finalize(), 0x220d);
+}
+
+#[test_case]
+fn test_internet_checksum_large_input() {
+    // 128K words of 0xf0f0 add up to more than u32::MAX
+    let block = [0xf0; 256];
+    let mut checksum = InternetChecksum::new();
+    for _ in 0..1024 {
+        checksum.update(&block);
+    }
+    assert_eq!(checksum.finalize(), 0x1e1e);
+}
@@ -0,0 +1,24 @@
+pub mod aes;
+pub mod hmac;
+pub mod sha256;
+
+pub use aes::Aes128;
+pub use hmac::HmacSha256;
+pub use sha256::Sha256;
+
+/// Compares two byte slices in time that depends only on their lengths, not
+/// on where they differ.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    diff == 0
+}
+
+#[test_case]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"tiny_os", b"tiny_os"));
+    assert!(!constant_time_eq(b"tiny_os", b"tiny_oS"));
+    assert!(!constant_time_eq(b"tiny", b"tiny_os"));
+}
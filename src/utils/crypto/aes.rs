@@ -0,0 +1,191 @@
+pub const BLOCK_LEN: usize = 16;
+pub const KEY_LEN: usize = 16;
+
+const ROUNDS: usize = 10;
+const RCON: [u8; ROUNDS] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+// The S-box is computed from the field inverse instead of being looked up in
+// a table, so that no memory access depends on key or data bytes.
+
+/// Multiplies by x in GF(2^8) without branching on the input.
+fn xtime(a: u8) -> u8 {
+    (a << 1) ^ (0x1b & 0u8.wrapping_sub(a >> 7))
+}
+
+fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// Computes a^254, which is the multiplicative inverse for a != 0 and 0 for 0.
+fn gf_inverse(a: u8) -> u8 {
+    let a2 = gmul(a, a);
+    let a3 = gmul(a2, a);
+    let a6 = gmul(a3, a3);
+    let a12 = gmul(a6, a6);
+    let a15 = gmul(a12, a3);
+    let a30 = gmul(a15, a15);
+    let a60 = gmul(a30, a30);
+    let a120 = gmul(a60, a60);
+    let a240 = gmul(a120, a120);
+    let a252 = gmul(a240, a12);
+    gmul(a252, a2)
+}
+
+fn sub_byte(a: u8) -> u8 {
+    let b = gf_inverse(a);
+    b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63
+}
+
+fn inv_sub_byte(a: u8) -> u8 {
+    gf_inverse(a.rotate_left(1) ^ a.rotate_left(3) ^ a.rotate_left(6) ^ 0x05)
+}
+
+/// AES-128 block cipher (FIPS-197) with a precomputed key schedule.
+#[derive(Clone)]
+pub struct Aes128 {
+    round_keys: [[u8; BLOCK_LEN]; ROUNDS + 1],
+}
+
+impl Aes128 {
+    pub fn new(key: &[u8; KEY_LEN]) -> Aes128 {
+        let mut round_keys = [[0u8; BLOCK_LEN]; ROUNDS + 1];
+        round_keys[0] = *key;
+
+        for round in 1..=ROUNDS {
+            let previous = round_keys[round - 1];
+            let mut word = [previous[13], previous[14], previous[15], previous[12]];
+            for byte in word.iter_mut() {
+                *byte = sub_byte(*byte);
+            }
+            word[0] ^= RCON[round - 1];
+
+            let key = &mut round_keys[round];
+            for i in 0..BLOCK_LEN {
+                key[i] = previous[i] ^ if i < 4 { word[i] } else { key[i - 4] };
+            }
+        }
+
+        Aes128 { round_keys }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_LEN]) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..ROUNDS {
+            sub_bytes(block);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+        sub_bytes(block);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[ROUNDS]);
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8; BLOCK_LEN]) {
+        add_round_key(block, &self.round_keys[ROUNDS]);
+        for round in (1..ROUNDS).rev() {
+            inv_shift_rows(block);
+            inv_sub_bytes(block);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        inv_sub_bytes(block);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+// The state is kept in the input byte order, so byte `4 * column + row`.
+
+fn add_round_key(block: &mut [u8; BLOCK_LEN], key: &[u8; BLOCK_LEN]) {
+    for (byte, key_byte) in block.iter_mut().zip(key) {
+        *byte ^= key_byte;
+    }
+}
+
+fn sub_bytes(block: &mut [u8; BLOCK_LEN]) {
+    for byte in block.iter_mut() {
+        *byte = sub_byte(*byte);
+    }
+}
+
+fn inv_sub_bytes(block: &mut [u8; BLOCK_LEN]) {
+    for byte in block.iter_mut() {
+        *byte = inv_sub_byte(*byte);
+    }
+}
+
+fn shift_rows(block: &mut [u8; BLOCK_LEN]) {
+    let state = *block;
+    for column in 0..4 {
+        for row in 0..4 {
+            block[4 * column + row] = state[4 * ((column + row) % 4) + row];
+        }
+    }
+}
+
+fn inv_shift_rows(block: &mut [u8; BLOCK_LEN]) {
+    let state = *block;
+    for column in 0..4 {
+        for row in 0..4 {
+            block[4 * ((column + row) % 4) + row] = state[4 * column + row];
+        }
+    }
+}
+
+fn mix_columns(block: &mut [u8; BLOCK_LEN]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(block: &mut [u8; BLOCK_LEN]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        column[0] = gmul(a0, 14) ^ gmul(a1, 11) ^ gmul(a2, 13) ^ gmul(a3, 9);
+        column[1] = gmul(a0, 9) ^ gmul(a1, 14) ^ gmul(a2, 11) ^ gmul(a3, 13);
+        column[2] = gmul(a0, 13) ^ gmul(a1, 9) ^ gmul(a2, 14) ^ gmul(a3, 11);
+        column[3] = gmul(a0, 11) ^ gmul(a1, 13) ^ gmul(a2, 9) ^ gmul(a3, 14);
+    }
+}
+
+#[test_case]
+fn test_aes_sbox_known_values() {
+    assert_eq!(sub_byte(0x00), 0x63);
+    assert_eq!(sub_byte(0x53), 0xed);
+    assert_eq!(inv_sub_byte(0xed), 0x53);
+}
+
+#[test_case]
+fn test_aes128_fips197_vector() {
+    let key = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+    let plaintext = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        0xff,
+    ];
+    let ciphertext = [
+        0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5,
+        0x5a,
+    ];
+
+    let aes = Aes128::new(&key);
+    let mut block = plaintext;
+    aes.encrypt_block(&mut block);
+    assert_eq!(block, ciphertext);
+    aes.decrypt_block(&mut block);
+    assert_eq!(block, plaintext);
+}
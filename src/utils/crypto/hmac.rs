@@ -0,0 +1,88 @@
+use super::constant_time_eq;
+use super::sha256::{Sha256, BLOCK_LEN, DIGEST_LEN};
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+/// Incremental HMAC-SHA256 (RFC 2104).
+#[derive(Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer_key: [u8; BLOCK_LEN],
+}
+
+impl HmacSha256 {
+    pub fn new(key: &[u8]) -> HmacSha256 {
+        let mut block_key = [0u8; BLOCK_LEN];
+        if key.len() > BLOCK_LEN {
+            block_key[..DIGEST_LEN].copy_from_slice(&Sha256::digest(key));
+        } else {
+            block_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner_key = block_key;
+        let mut outer_key = block_key;
+        for byte in inner_key.iter_mut() {
+            *byte ^= IPAD;
+        }
+        for byte in outer_key.iter_mut() {
+            *byte ^= OPAD;
+        }
+
+        let mut inner = Sha256::new();
+        inner.update(&inner_key);
+        HmacSha256 { inner, outer_key }
+    }
+
+    /// Computes the MAC of `data` under `key` in one go.
+    pub fn mac(key: &[u8], data: &[u8]) -> [u8; DIGEST_LEN] {
+        let mut hmac = HmacSha256::new(key);
+        hmac.update(data);
+        hmac.finalize()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; DIGEST_LEN] {
+        let inner_digest = self.inner.finalize();
+        let mut outer = Sha256::new();
+        outer.update(&self.outer_key);
+        outer.update(&inner_digest);
+        outer.finalize()
+    }
+
+    /// Checks the MAC against `expected` without leaking where they differ.
+    pub fn verify(self, expected: &[u8]) -> bool {
+        constant_time_eq(&self.finalize(), expected)
+    }
+}
+
+#[test_case]
+fn test_hmac_sha256_rfc4231_case_2() {
+    let expected = [
+        0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75,
+        0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec,
+        0x38, 0x43,
+    ];
+    assert_eq!(
+        HmacSha256::mac(b"Jefe", b"what do ya want for nothing?"),
+        expected
+    );
+}
+
+#[test_case]
+fn test_hmac_sha256_long_key_and_verify() {
+    // RFC 4231 test case 6: the key is longer than a block and gets hashed
+    let key = [0xaa; 131];
+    let expected = [
+        0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5, 0xb7,
+        0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f, 0x0e, 0xe3,
+        0x7f, 0x54,
+    ];
+    let mut hmac = HmacSha256::new(&key);
+    hmac.update(b"Test Using Larger Than Block-Size Key - Hash Key First");
+    assert!(hmac.clone().verify(&expected));
+    assert!(!hmac.verify(&[0; DIGEST_LEN]));
+}
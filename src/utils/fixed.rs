@@ -0,0 +1,325 @@
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use crate::utils::collections::String;
+
+const FRACTION_BITS: u32 = 16;
+const ONE_RAW: i32 = 1 << FRACTION_BITS;
+
+/// Signed Q16.16 fixed-point number: 16 integer bits and 16 fraction bits,
+/// covering about -32768.0 to 32767.99998 in steps of 1/65536.
+///
+/// `+`, `-` and `abs` overflow like `i32` arithmetic; `*`, `/`, `from_ratio`
+/// and `lerp` panic on overflow, use the `checked_*` and `saturating_*`
+/// variants where that can happen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(ONE_RAW);
+    pub const HALF: Fixed = Fixed(ONE_RAW / 2);
+    pub const MIN: Fixed = Fixed(i32::MIN);
+    pub const MAX: Fixed = Fixed(i32::MAX);
+
+    pub const fn from_raw(raw: i32) -> Fixed {
+        Fixed(raw)
+    }
+
+    pub const fn to_raw(self) -> i32 {
+        self.0
+    }
+
+    pub const fn from_int(value: i16) -> Fixed {
+        Fixed((value as i32) << FRACTION_BITS)
+    }
+
+    /// Returns `numerator / denominator`, rounded towards zero.
+    ///
+    /// Panics if the denominator is zero or the result is out of range.
+    pub const fn from_ratio(numerator: i32, denominator: i32) -> Fixed {
+        match Fixed::checked_from_ratio(numerator, denominator) {
+            Some(value) => value,
+            None => panic!("fixed-point ratio overflowed or divided by zero"),
+        }
+    }
+
+    /// Like `from_ratio`, but returns `None` instead of panicking.
+    pub const fn checked_from_ratio(numerator: i32, denominator: i32) -> Option<Fixed> {
+        if denominator == 0 {
+            return None;
+        }
+        let quotient = ((numerator as i64) << FRACTION_BITS) / denominator as i64;
+        if quotient < i32::MIN as i64 || quotient > i32::MAX as i64 {
+            return None;
+        }
+        Some(Fixed(quotient as i32))
+    }
+
+    /// Largest integer less than or equal to the value.
+    pub const fn floor(self) -> i32 {
+        self.0 >> FRACTION_BITS
+    }
+
+    /// Nearest integer, with halves rounded up.
+    pub const fn round(self) -> i32 {
+        ((self.0 as i64 + ONE_RAW as i64 / 2) >> FRACTION_BITS) as i32
+    }
+
+    /// Fractional part, always in `[0, 1)`.
+    pub const fn fract(self) -> Fixed {
+        Fixed(self.0 & (ONE_RAW - 1))
+    }
+
+    /// Absolute value. Like `i32::abs`, overflows for `Fixed::MIN` (a panic in
+    /// debug builds).
+    pub const fn abs(self) -> Fixed {
+        Fixed(self.0.abs())
+    }
+
+    pub const fn checked_abs(self) -> Option<Fixed> {
+        match self.0.checked_abs() {
+            Some(raw) => Some(Fixed(raw)),
+            None => None,
+        }
+    }
+
+    pub const fn saturating_abs(self) -> Fixed {
+        Fixed(self.0.saturating_abs())
+    }
+
+    pub fn checked_mul(self, rhs: Fixed) -> Option<Fixed> {
+        let product = (self.0 as i64 * rhs.0 as i64) >> FRACTION_BITS;
+        i32::try_from(product).ok().map(Fixed)
+    }
+
+    pub fn checked_div(self, rhs: Fixed) -> Option<Fixed> {
+        if rhs.0 == 0 {
+            return None;
+        }
+        let quotient = ((self.0 as i64) << FRACTION_BITS) / rhs.0 as i64;
+        i32::try_from(quotient).ok().map(Fixed)
+    }
+
+    pub fn saturating_mul(self, rhs: Fixed) -> Fixed {
+        let product = (self.0 as i64 * rhs.0 as i64) >> FRACTION_BITS;
+        Fixed(product.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+
+    /// Square root, rounded down; `None` for negative values.
+    pub fn sqrt(self) -> Option<Fixed> {
+        if self.0 < 0 {
+            return None;
+        }
+        // sqrt(raw / 2^16) * 2^16 == sqrt(raw * 2^16)
+        Some(Fixed(isqrt((self.0 as u64) << FRACTION_BITS) as i32))
+    }
+
+    /// Linear interpolation between `self` (at `t` = 0) and `other` (at 1).
+    ///
+    /// `t` outside `[0, 1]` extrapolates; panics if the result is out of
+    /// range.
+    pub fn lerp(self, other: Fixed, t: Fixed) -> Fixed {
+        self.checked_lerp(other, t)
+            .expect("fixed-point interpolation overflowed")
+    }
+
+    pub fn checked_lerp(self, other: Fixed, t: Fixed) -> Option<Fixed> {
+        let delta = (other.0 as i64 - self.0 as i64).checked_mul(t.0 as i64)?;
+        i32::try_from(self.0 as i64 + (delta >> FRACTION_BITS))
+            .ok()
+            .map(Fixed)
+    }
+}
+
+/// Integer square root, rounded down.
+fn isqrt(value: u64) -> u64 {
+    let mut remainder = value;
+    let mut root = 0u64;
+    let mut bit = 1u64 << 62;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+impl From<i16> for Fixed {
+    fn from(value: i16) -> Fixed {
+        Fixed::from_int(value)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, rhs: Fixed) -> Fixed {
+        self.checked_mul(rhs)
+            .expect("fixed-point multiplication overflowed")
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    fn div(self, rhs: Fixed) -> Fixed {
+        self.checked_div(rhs)
+            .expect("fixed-point division overflowed or divided by zero")
+    }
+}
+
+impl fmt::Display for Fixed {
+    /// Prints the value in decimal, with four fraction digits unless a
+    /// precision is given. Width, fill, alignment and the `+` and `0` flags
+    /// work as for integers.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use core::fmt::Write;
+
+        let precision = f.precision().unwrap_or(4).min(9);
+        let raw = self.0 as i64;
+        let scale = 10u64.pow(precision as u32);
+        // round to the requested number of digits
+        let scaled = (raw.unsigned_abs() * scale + (ONE_RAW as u64 / 2)) >> FRACTION_BITS;
+        let is_nonnegative = raw >= 0 || scaled == 0;
+        let integer = scaled / scale;
+
+        // at most five integer digits, the point and nine fraction digits
+        let mut digits = String::<16>::new();
+        if precision == 0 {
+            write!(digits, "{}", integer)?;
+        } else {
+            let fraction = scaled % scale;
+            write!(
+                digits,
+                "{}.{:0width$}",
+                integer,
+                fraction,
+                width = precision
+            )?;
+        }
+        // unlike `pad`, `pad_integral` does not treat the precision as a
+        // maximum length
+        f.pad_integral(is_nonnegative, "", &digits)
+    }
+}
+
+#[test_case]
+fn test_fixed_arithmetic() {
+    let a = Fixed::from_ratio(3, 2);
+    let b = Fixed::from_int(-2);
+    assert_eq!(a + b, Fixed::from_ratio(-1, 2));
+    assert_eq!(a * b, Fixed::from_int(-3));
+    assert_eq!(b / a, Fixed::from_ratio(-4, 3));
+    assert_eq!(Fixed::ONE.checked_div(Fixed::ZERO), None);
+    assert_eq!(Fixed::MAX.checked_mul(Fixed::from_int(2)), None);
+    assert_eq!(Fixed::checked_from_ratio(100000, 1), None);
+    assert_eq!(Fixed::checked_from_ratio(1, 0), None);
+    assert_eq!(Fixed::checked_from_ratio(-32768, 1), Some(Fixed::MIN));
+    assert_eq!(Fixed::MIN.checked_abs(), None);
+    assert_eq!(Fixed::MIN.saturating_abs(), Fixed::MAX);
+}
+
+#[test_case]
+fn test_fixed_rounding() {
+    let value = Fixed::from_ratio(-5, 4);
+    assert_eq!(value.floor(), -2);
+    assert_eq!(value.round(), -1);
+    assert_eq!(value.fract(), Fixed::from_ratio(3, 4));
+    assert_eq!(Fixed::from_ratio(5, 2).round(), 3);
+}
+
+#[test_case]
+fn test_fixed_sqrt_and_lerp() {
+    assert_eq!(Fixed::from_int(9).sqrt(), Some(Fixed::from_int(3)));
+    assert_eq!(Fixed::from_int(2).sqrt(), Some(Fixed::from_raw(92681)));
+    assert_eq!(Fixed::from_int(-1).sqrt(), None);
+
+    let start = Fixed::from_int(10);
+    let end = Fixed::from_int(20);
+    assert_eq!(start.lerp(end, Fixed::ZERO), start);
+    assert_eq!(start.lerp(end, Fixed::ONE), end);
+    assert_eq!(
+        start.lerp(end, Fixed::from_ratio(1, 4)),
+        Fixed::from_ratio(25, 2)
+    );
+    assert_eq!(start.lerp(end, Fixed::from_int(2)), Fixed::from_int(30));
+    assert_eq!(
+        Fixed::ZERO.checked_lerp(Fixed::from_int(30000), Fixed::from_int(2)),
+        None
+    );
+    assert_eq!(Fixed::MIN.checked_lerp(Fixed::MAX, Fixed::MAX), None);
+}
+
+#[test_case]
+fn test_fixed_display() {
+    use core::fmt::Write;
+
+    let mut buffer = String::<64>::new();
+    write!(
+        buffer,
+        "{} {} {:.2} {:.0}",
+        Fixed::from_ratio(3, 2),
+        Fixed::from_ratio(-1, 8),
+        Fixed::from_int(2).sqrt().unwrap(),
+        Fixed::from_ratio(7, 2)
+    )
+    .unwrap();
+    assert_eq!(buffer, "1.5000 -0.1250 1.41 4");
+
+    buffer.clear();
+    write!(
+        buffer,
+        "[{:>10}] [{:<7.1}] [{:+.2}] [{:08.2}]",
+        Fixed::ONE,
+        Fixed::HALF,
+        Fixed::HALF,
+        Fixed::from_ratio(-3, 2)
+    )
+    .unwrap();
+    assert_eq!(buffer, "[    1.0000] [0.5    ] [+0.50] [-0001.50]");
+}
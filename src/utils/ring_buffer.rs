@@ -0,0 +1,270 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// Both buffers use free-running positions that are only reduced to a slot
+// index on access, so capacities must be powers of two to stay correct when
+// the counters wrap around.
+
+/// Bounded single-producer single-consumer queue.
+///
+/// The buffer is split into a `Producer` and a `Consumer` handle that work
+/// without locking. To use them from different contexts, e.g. an interrupt
+/// handler and the main loop, put the buffer in a `static mut` (`new` is a
+/// `const fn`) and split it once through a `&'static mut` reference, which
+/// gives `'static` handles.
+pub struct SpscRingBuffer<T, const N: usize> {
+    /// Slots are only accessed through raw pointers, so the producer and the
+    /// consumer never hold references to the whole array.
+    buffer: UnsafeCell<MaybeUninit<[T; N]>>,
+    /// Position of the next element to pop.
+    head: AtomicUsize,
+    /// Position of the next element to push.
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for SpscRingBuffer<T, N> {}
+
+impl<T, const N: usize> SpscRingBuffer<T, N> {
+    const CAPACITY_IS_POWER_OF_TWO: () = assert!(N.is_power_of_two());
+
+    pub const fn new() -> SpscRingBuffer<T, N> {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::CAPACITY_IS_POWER_OF_TWO;
+        SpscRingBuffer {
+            buffer: UnsafeCell::new(MaybeUninit::uninit()),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Splits the buffer into its producer and consumer ends. The handles
+    /// borrow the buffer, so splitting a `&'static mut` gives handles that
+    /// can be stored in statics or moved into an interrupt handler.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { ring: self }, Consumer { ring: self })
+    }
+
+    fn slot(&self, pos: usize) -> *mut T {
+        unsafe { (self.buffer.get() as *mut T).add(pos & (N - 1)) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Default for SpscRingBuffer<T, N> {
+    fn default() -> Self {
+        SpscRingBuffer::new()
+    }
+}
+
+impl<T, const N: usize> Drop for SpscRingBuffer<T, N> {
+    fn drop(&mut self) {
+        let (_, mut consumer) = self.split();
+        while consumer.pop().is_some() {}
+    }
+}
+
+pub struct Producer<'a, T, const N: usize> {
+    ring: &'a SpscRingBuffer<T, N>,
+}
+
+impl<'a, T, const N: usize> Producer<'a, T, N> {
+    /// Appends `value`, handing it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let head = self.ring.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == N {
+            return Err(value);
+        }
+        unsafe { self.ring.slot(tail).write(value) };
+        self.ring
+            .tail
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+pub struct Consumer<'a, T, const N: usize> {
+    ring: &'a SpscRingBuffer<T, N>,
+}
+
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    /// Removes the oldest element, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let value = unsafe { self.ring.slot(head).read() };
+        self.ring
+            .head
+            .store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+}
+
+struct Slot<T> {
+    /// Equals the slot's position while free and position + 1 once written.
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Bounded multi-producer queue (Vyukov's array-based design).
+///
+/// `push` may be called concurrently from any number of contexts, including
+/// interrupt handlers, without locking. `pop` is also safe to call
+/// concurrently, but the intended use is a single consumer draining it.
+pub struct MpscRingBuffer<T, const N: usize> {
+    slots: [Slot<T>; N],
+    enqueue_pos: AtomicUsize,
+    dequeue_pos: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for MpscRingBuffer<T, N> {}
+
+impl<T, const N: usize> MpscRingBuffer<T, N> {
+    const CAPACITY_IS_POWER_OF_TWO: () = assert!(N.is_power_of_two());
+
+    pub fn new() -> MpscRingBuffer<T, N> {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::CAPACITY_IS_POWER_OF_TWO;
+        MpscRingBuffer {
+            slots: core::array::from_fn(|i| Slot {
+                sequence: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }),
+            enqueue_pos: AtomicUsize::new(0),
+            dequeue_pos: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Appends `value`, handing it back if the buffer is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & (N - 1)];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let diff = sequence.wrapping_sub(pos) as isize;
+            if diff == 0 {
+                match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.sequence.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // the slot still holds the element from one lap ago
+                return Err(value);
+            } else {
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Removes the oldest element, if any.
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & (N - 1)];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let diff = sequence.wrapping_sub(pos.wrapping_add(1)) as isize;
+            if diff == 0 {
+                match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.sequence.store(pos.wrapping_add(N), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                return None;
+            } else {
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for MpscRingBuffer<T, N> {
+    fn default() -> Self {
+        MpscRingBuffer::new()
+    }
+}
+
+impl<T, const N: usize> Drop for MpscRingBuffer<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[test_case]
+fn test_spsc_push_pop_in_order() {
+    let mut ring = SpscRingBuffer::<u32, 4>::new();
+    let (mut producer, mut consumer) = ring.split();
+    for value in 0..4 {
+        assert_eq!(producer.push(value), Ok(()));
+    }
+    assert_eq!(producer.push(4), Err(4));
+    assert_eq!(consumer.pop(), Some(0));
+    assert_eq!(producer.push(4), Ok(()));
+    for value in 1..5 {
+        assert_eq!(consumer.pop(), Some(value));
+    }
+    assert_eq!(consumer.pop(), None);
+}
+
+#[test_case]
+fn test_spsc_split_static() {
+    static mut RING: SpscRingBuffer<u8, 2> = SpscRingBuffer::new();
+    let (mut producer, mut consumer): (Producer<'static, u8, 2>, Consumer<'static, u8, 2>) =
+        unsafe { (*core::ptr::addr_of_mut!(RING)).split() };
+    assert_eq!(producer.push(1), Ok(()));
+    assert_eq!(consumer.pop(), Some(1));
+    assert_eq!(consumer.pop(), None);
+}
+
+#[test_case]
+fn test_mpsc_push_pop_wraps_around() {
+    let ring = MpscRingBuffer::<usize, 8>::new();
+    for round in 0..3 {
+        for i in 0..8 {
+            assert_eq!(ring.push(round * 8 + i), Ok(()));
+        }
+        assert_eq!(ring.push(usize::MAX), Err(usize::MAX));
+        for i in 0..8 {
+            assert_eq!(ring.pop(), Some(round * 8 + i));
+        }
+        assert_eq!(ring.pop(), None);
+    }
+}